@@ -1,8 +1,13 @@
+use flate2::read::GzDecoder;
 use pyo3::exceptions::PyIOError;
 use pyo3::prelude::*;
+use ruzstd::decoding::StreamingDecoder as ZstdStreamingDecoder;
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use unicode_normalization::char::canonical_combining_class;
+use unicode_normalization::UnicodeNormalization;
 
 // Constants for memory control
 const CHUNK_SIZE: usize = 8192; // 8KB per chunk
@@ -23,6 +28,8 @@ fn normalize_encoding_name(encoding: &str) -> String {
         "utf_16" | "utf16" => "utf_16".to_string(),
         "utf_16_le" | "utf16_le" | "utf_16le" | "utf16le" => "utf_16le".to_string(),
         "utf_16_be" | "utf16_be" | "utf_16be" | "utf16be" => "utf_16be".to_string(),
+        "utf_32_le" | "utf32_le" | "utf_32le" | "utf32le" => "utf_32le".to_string(),
+        "utf_32_be" | "utf32_be" | "utf_32be" | "utf32be" => "utf_32be".to_string(),
         "iso_8859_1" | "iso8859_1" | "latin_1" | "latin1" => "latin_1".to_string(),
         "windows_1252" | "cp_1252" => "cp1252".to_string(),
         "windows_1256" | "cp_1256" => "cp1256".to_string(),
@@ -55,14 +62,16 @@ struct AnalysisResult {
     encoding: String,
     #[pyo3(get)]
     newlines: String,
+    #[pyo3(get)]
+    confidence: f32,
 }
 
 #[pymethods]
 impl AnalysisResult {
     fn __repr__(&self) -> PyResult<String> {
         Ok(format!(
-            "AnalysisResult(encoding='{}', newlines='{}')",
-            self.encoding, self.newlines
+            "AnalysisResult(encoding='{}', newlines='{}', confidence={})",
+            self.encoding, self.newlines, self.confidence
         ))
     }
 }
@@ -182,73 +191,214 @@ fn detect_utf16_pattern(buffer: &[u8]) -> Option<&'static str> {
     None
 }
 
-// Detect language characteristics from decoded text
-fn detect_language_hints(text: &str) -> Vec<&'static str> {
-    let mut hints = Vec::new();
+// Detects stateful escape-sequence encodings, which are 7-bit-clean and so
+// never trip the high-byte heuristics in `analyze_byte_patterns`. Scans for
+// the ISO-2022-JP designator escapes and the HZ-GB2312 `~{`/`~}` shift
+// markers; an ISO-2022-JP hit is only trusted once `encoding_rs` confirms it
+// decodes the sample cleanly, since the escape bytes alone are also valid
+// plain ASCII. HZ-GB2312 has no `encoding_rs` codec to verify against, so a
+// marker match is trusted on its own.
+fn detect_escape_based_encoding(buffer: &[u8]) -> Option<&'static str> {
+    const ISO2022JP_ESCAPES: &[&[u8]] =
+        &[b"\x1b$@", b"\x1b$B", b"\x1b(B", b"\x1b(J"];
+
+    if ISO2022JP_ESCAPES.iter().any(|esc| {
+        buffer
+            .windows(esc.len())
+            .any(|window| window == *esc)
+    }) {
+        if let Some(encoding) = encoding_rs::Encoding::for_label(b"iso-2022-jp") {
+            let (_decoded, _, had_errors) = encoding.decode(buffer);
+            if !had_errors {
+                return Some("ISO-2022-JP");
+            }
+        }
+    }
 
-    let total_chars = text.chars().count().max(1);
+    if buffer.windows(2).any(|w| w == b"~{") && buffer.windows(2).any(|w| w == b"~}") {
+        return Some("HZ-GB2312");
+    }
 
-    let arabic_chars = text
-        .chars()
-        .filter(|c| {
-            let code = *c as u32;
-            // Arabic block + Arabic Presentation Forms
-            (code >= 0x0600 && code <= 0x06FF)
-                || (code >= 0xFB50 && code <= 0xFDFF)
-                || (code >= 0xFE70 && code <= 0xFEFF)
-        })
-        .count();
+    None
+}
 
-    let cyrillic_chars = text
-        .chars()
-        .filter(|c| {
-            let code = *c as u32;
-            (code >= 0x0400 && code <= 0x04FF) || (code >= 0x0500 && code <= 0x052F)
-        })
-        .count();
+// Character-class buckets used by the adjacency scoring engine below.
+// Modeled loosely on chardetng's per-encoding class-transition tables:
+// encodings are scored by how plausible it is for two adjacent classes to
+// appear next to each other, rather than by a handful of per-language ratios.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    AsciiSpacePunct,
+    AsciiDigit,
+    AsciiLetter,
+    LatinLower,
+    LatinUpper,
+    NonLatinLetter(Script),
+    Other,
+}
 
-    let turkish_specific = text
-        .chars()
-        .filter(|c| {
-            // Turkish-specific letters that don't appear in other Latin scripts
-            matches!(*c, 'ğ' | 'Ğ' | 'ı' | 'İ' | 'ş' | 'Ş')
-        })
-        .count();
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Cyrillic,
+    Arabic,
+    Greek,
+    Hebrew,
+    Hangul,
+    Han,
+    Hiragana,
+    Katakana,
+}
 
-    let korean_chars = text
-        .chars()
-        .filter(|c| {
-            let code = *c as u32;
-            // Hangul Syllables + Hangul Jamo
-            (code >= 0xAC00 && code <= 0xD7AF)
-                || (code >= 0x1100 && code <= 0x11FF)
-                || (code >= 0x3130 && code <= 0x318F)
-        })
-        .count();
+// Coarse script classifier for non-ASCII letters. Returns `None` for
+// extended-Latin letters (accented Latin, which get their own bucket) and
+// for anything we don't specifically track.
+fn script_of(code: u32) -> Option<Script> {
+    match code {
+        0x0400..=0x04FF | 0x0500..=0x052F => Some(Script::Cyrillic),
+        0x0600..=0x06FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Some(Script::Arabic),
+        0x0370..=0x03FF => Some(Script::Greek),
+        0x0590..=0x05FF => Some(Script::Hebrew),
+        0xAC00..=0xD7AF | 0x1100..=0x11FF | 0x3130..=0x318F => Some(Script::Hangul),
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF => Some(Script::Han),
+        0x3040..=0x309F => Some(Script::Hiragana),
+        0x30A0..=0x30FF => Some(Script::Katakana),
+        _ => None,
+    }
+}
 
-    // Calculate percentages
-    let arabic_ratio = arabic_chars as f32 / total_chars as f32;
-    let cyrillic_ratio = cyrillic_chars as f32 / total_chars as f32;
-    let korean_ratio = korean_chars as f32 / total_chars as f32;
+fn is_latin_extended(code: u32) -> bool {
+    matches!(code, 0x00C0..=0x024F | 0x1E00..=0x1EFF)
+}
 
-    // Arabic text typically has high ratio of Arabic characters
-    if arabic_ratio > 0.3 {
-        hints.push("arabic");
-    }
-    // Cyrillic, but not if there's more Arabic
-    if cyrillic_ratio > 0.2 && arabic_ratio < 0.1 {
-        hints.push("cyrillic");
-    }
-    // Turkish needs at least a few specific chars
-    if turkish_specific >= 3 {
-        hints.push("turkish");
+// A char is "isolated combining/format" when a combining mark or a
+// zero-width format character shows up without a preceding base character,
+// which is exactly the shape a decoder produces when it lands on a
+// continuation-style byte that doesn't belong to a valid sequence.
+fn is_combining_or_format(c: char) -> bool {
+    let code = c as u32;
+    matches!(code, 0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F | 0xFEFF)
+}
+
+fn classify_char(c: char) -> CharClass {
+    if c.is_ascii() {
+        if c.is_ascii_alphabetic() {
+            CharClass::AsciiLetter
+        } else if c.is_ascii_digit() {
+            CharClass::AsciiDigit
+        } else {
+            CharClass::AsciiSpacePunct
+        }
+    } else if let Some(script) = script_of(c as u32) {
+        CharClass::NonLatinLetter(script)
+    } else if is_latin_extended(c as u32) {
+        if c.is_uppercase() {
+            CharClass::LatinUpper
+        } else {
+            CharClass::LatinLower
+        }
+    } else {
+        CharClass::Other
     }
-    // Korean text has very high ratio of Korean chars
-    if korean_ratio > 0.2 {
-        hints.push("korean");
+}
+
+// Adjacency-based plausibility score for a decoded candidate, modeled on
+// Mozilla's chardetng: walk adjacent non-space characters and accumulate
+// penalties for implausible class transitions and bonuses for plausible
+// ones, rather than relying on hand-picked per-language ratios.
+fn score_char_transitions(text: &str) -> i64 {
+    const IMPLAUSIBLE_SEQUENCE_PENALTY: i64 = -220;
+    const SCRIPT_MIX_PENALTY: i64 = -50;
+    const IMPLAUSIBLE_CASE_PENALTY: i64 = -180;
+    const ORDINAL_BONUS: i64 = 300;
+    const COPYRIGHT_BONUS: i64 = 222;
+    const NON_LATIN_CAPITALIZATION_BONUS: i64 = 40;
+    const REPLACEMENT_PENALTY: i64 = -500;
+
+    let mut score: i64 = 0;
+    let mut prev: Option<char> = None;
+
+    for c in text.chars() {
+        if c == '\u{FFFD}' {
+            score += REPLACEMENT_PENALTY;
+            prev = None;
+            continue;
+        }
+
+        if c == '\u{00A9}' {
+            // Correctly decoded copyright sign: strong signal the byte
+            // sequence that produced it was intentional, not coincidence.
+            score += COPYRIGHT_BONUS;
+        }
+
+        // Ordinal indicators / degree-like marks directly after a digit
+        // (1º, 2ª, 30°, ...) are a reliable Latin-1-family fingerprint.
+        if matches!(c, '\u{00BA}' | '\u{00AA}' | '\u{00B0}' | '\u{00B9}' | '\u{00B2}' | '\u{00B3}')
+        {
+            if let Some(p) = prev {
+                if p.is_ascii_digit() {
+                    score += ORDINAL_BONUS;
+                }
+            }
+        }
+
+        if is_combining_or_format(c) && prev.map_or(true, |p| !classify_char(p).is_letter()) {
+            score += IMPLAUSIBLE_SEQUENCE_PENALTY;
+        }
+
+        let class = classify_char(c);
+
+        if class != CharClass::AsciiSpacePunct {
+            if let Some(p) = prev {
+                let prev_class = classify_char(p);
+
+                let script_mix = matches!(
+                    (prev_class, class),
+                    (CharClass::LatinLower, CharClass::NonLatinLetter(_))
+                        | (CharClass::LatinUpper, CharClass::NonLatinLetter(_))
+                        | (CharClass::NonLatinLetter(_), CharClass::LatinLower)
+                        | (CharClass::NonLatinLetter(_), CharClass::LatinUpper)
+                ) || matches!(
+                    (prev_class, class),
+                    (CharClass::NonLatinLetter(a), CharClass::NonLatinLetter(b)) if a != b
+                );
+                if script_mix {
+                    score += SCRIPT_MIX_PENALTY;
+                }
+
+                if p.is_lowercase() && c.is_uppercase() {
+                    score += IMPLAUSIBLE_CASE_PENALTY;
+                }
+
+                if let (CharClass::NonLatinLetter(a), CharClass::NonLatinLetter(b)) =
+                    (prev_class, class)
+                {
+                    if a == b && p.is_uppercase() && c.is_lowercase() {
+                        score += NON_LATIN_CAPITALIZATION_BONUS;
+                    }
+                }
+            }
+        }
+
+        prev = if class == CharClass::AsciiSpacePunct {
+            None
+        } else {
+            Some(c)
+        };
     }
 
-    hints
+    score
+}
+
+impl CharClass {
+    fn is_letter(self) -> bool {
+        matches!(
+            self,
+            CharClass::AsciiLetter
+                | CharClass::LatinLower
+                | CharClass::LatinUpper
+                | CharClass::NonLatinLetter(_)
+        )
+    }
 }
 
 /// Calculate the effective sample size based on file size and parameters
@@ -343,22 +493,525 @@ fn read_strategic_sample(
     Ok(buffer)
 }
 
-/// Analyzes encoding and newline style from a file using streaming
-#[pyfunction]
-#[pyo3(signature = (file_path, min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None))]
-fn analyse_from_path_stream(
-    file_path: String,
-    min_sample_size: usize,
-    percentage_sample_size: f64,
-    max_sample_size: Option<usize>,
-) -> PyResult<AnalysisResult> {
-    let path = Path::new(&file_path);
+// Legacy single-byte/CJK encoding family a `locale`/`tld` hint steers
+// detection towards, mirroring chardetng's TLD classifier. Each family
+// carries the set of `encodings_to_try` labels it should bias for and the
+// ones it should bias against (e.g. a ".ru" hint should never prefer an
+// Arabic codepage over a near-tied Cyrillic one).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocaleFamily {
+    Cyrillic,
+    Arabic,
+    Turkish,
+    Greek,
+    CentralEuropean,
+    Korean,
+    Japanese,
+    TraditionalChinese,
+    Simplified,
+}
+
+impl LocaleFamily {
+    fn preferred_labels(self) -> &'static [&'static str] {
+        match self {
+            LocaleFamily::Cyrillic => &["windows-1251", "x-mac-cyrillic", "koi8-r"],
+            LocaleFamily::Arabic => &["windows-1256"],
+            LocaleFamily::Turkish => &["windows-1254"],
+            LocaleFamily::Greek => &["windows-1253"],
+            LocaleFamily::CentralEuropean => &["windows-1250"],
+            LocaleFamily::Korean => &["windows-949", "euc-kr"],
+            LocaleFamily::Japanese => &["shift_jis", "euc-jp"],
+            LocaleFamily::TraditionalChinese => &["big5"],
+            LocaleFamily::Simplified => &["gbk"],
+        }
+    }
+}
+
+// Map a caller-supplied locale or TLD hint (e.g. "ru", ".ru", "tr-TR",
+// "ja") to a legacy-encoding family. Returns `None` for a generic/unknown
+// hint, in which case detection falls back entirely to the scoring engine.
+fn locale_hint_family(locale: &str) -> Option<LocaleFamily> {
+    let normalized = locale.trim().trim_start_matches('.').to_lowercase();
+    let primary = normalized.split(['-', '_']).next().unwrap_or(&normalized);
+
+    match primary {
+        "ru" | "rus" | "russian" | "uk" | "ukrainian" | "bg" | "bulgarian" | "sr" | "serbian" => {
+            Some(LocaleFamily::Cyrillic)
+        }
+        "ar" | "arabic" | "sa" | "eg" => Some(LocaleFamily::Arabic),
+        "tr" | "turkish" => Some(LocaleFamily::Turkish),
+        "el" | "greek" | "gr" => Some(LocaleFamily::Greek),
+        "pl" | "cz" | "cs" | "sk" | "hu" | "ro" | "hr" => Some(LocaleFamily::CentralEuropean),
+        "ko" | "kr" | "korean" => Some(LocaleFamily::Korean),
+        "ja" | "jp" | "japanese" => Some(LocaleFamily::Japanese),
+        "tw" | "hk" => Some(LocaleFamily::TraditionalChinese),
+        "zh" if normalized.starts_with("zh-tw") || normalized.starts_with("zh_tw") => {
+            Some(LocaleFamily::TraditionalChinese)
+        }
+        "zh" | "cn" | "chinese" => Some(LocaleFamily::Simplified),
+        _ => None,
+    }
+}
+
+// Compression format of an input file, either given explicitly by the caller
+// or sniffed from its leading magic bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+fn parse_compression(compression: &str) -> PyResult<Compression> {
+    match compression.to_lowercase().as_str() {
+        "none" => Ok(Compression::None),
+        "gzip" | "gz" => Ok(Compression::Gzip),
+        "zstd" | "zst" => Ok(Compression::Zstd),
+        other => Err(PyIOError::new_err(format!(
+            "Unknown compression '{}'. Must be 'none', 'gzip', or 'zstd'",
+            other
+        ))),
+    }
+}
+
+fn sniff_compression(header: &[u8]) -> Compression {
+    if header.starts_with(&GZIP_MAGIC) {
+        Compression::Gzip
+    } else if header.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    }
+}
+
+// Which MIME Content-Transfer-Encoding (if any) should be unwrapped before
+// the byte stream reaches charset analysis/decoding.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TransferEncoding {
+    None,
+    Base64,
+    QuotedPrintable,
+}
+
+fn parse_transfer_encoding(value: &str) -> PyResult<TransferEncoding> {
+    match value.to_lowercase().replace('-', "_").as_str() {
+        "none" => Ok(TransferEncoding::None),
+        "base64" => Ok(TransferEncoding::Base64),
+        "quoted_printable" | "qp" => Ok(TransferEncoding::QuotedPrintable),
+        other => Err(PyIOError::new_err(format!(
+            "Unknown transfer_encoding '{}'. Must be 'base64' or 'quoted-printable'",
+            other
+        ))),
+    }
+}
+
+// Decodes a base64 byte stream on the fly. Input is consumed in multiples
+// of 4 alphabet characters (whitespace/newlines are skipped rather than
+// treated as data); any 0-3 trailing characters that don't complete a group
+// yet are carried over to the next underlying read.
+struct Base64DecodeReader<R: Read> {
+    inner: R,
+    carry: Vec<u8>,
+    pending: std::collections::VecDeque<u8>,
+    inner_eof: bool,
+}
+
+impl<R: Read> Base64DecodeReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            carry: Vec::new(),
+            pending: std::collections::VecDeque::new(),
+            inner_eof: false,
+        }
+    }
+}
+
+fn base64_value(byte: u8) -> Option<u8> {
+    match byte {
+        b'A'..=b'Z' => Some(byte - b'A'),
+        b'a'..=b'z' => Some(byte - b'a' + 26),
+        b'0'..=b'9' => Some(byte - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+// Decodes one or more base64 groups. `group` is normally a multiple of 4 in
+// length, possibly with trailing `=` padding, but the final group passed at
+// EOF may be a short, unpadded 2- or 3-character remainder (a stream that
+// ends without `=` padding); a leftover single character can't encode any
+// bytes and is dropped.
+fn decode_base64_groups(group: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(group.len() / 4 * 3 + 2);
+    for chunk in group.chunks(4) {
+        if chunk.len() < 2 {
+            continue;
+        }
+        let explicit_pad = chunk.iter().filter(|&&b| b == b'=').count();
+        let pad = explicit_pad + (4 - chunk.len());
+        let values = [
+            chunk.first().and_then(|&b| base64_value(b)).unwrap_or(0),
+            chunk.get(1).and_then(|&b| base64_value(b)).unwrap_or(0),
+            chunk.get(2).and_then(|&b| base64_value(b)).unwrap_or(0),
+            chunk.get(3).and_then(|&b| base64_value(b)).unwrap_or(0),
+        ];
+        let combined = (values[0] as u32) << 18
+            | (values[1] as u32) << 12
+            | (values[2] as u32) << 6
+            | values[3] as u32;
+        output.push((combined >> 16) as u8);
+        if pad < 2 {
+            output.push((combined >> 8) as u8);
+        }
+        if pad < 1 {
+            output.push(combined as u8);
+        }
+    }
+    output
+}
+
+impl<R: Read> Read for Base64DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.is_empty() && !self.inner_eof {
+            let mut raw = [0u8; CHUNK_SIZE];
+            let n = self.inner.read(&mut raw)?;
+            if n == 0 {
+                self.inner_eof = true;
+                if !self.carry.is_empty() {
+                    let leftover = std::mem::take(&mut self.carry);
+                    self.pending.extend(decode_base64_groups(&leftover));
+                }
+                break;
+            }
+            self.carry
+                .extend(raw[..n].iter().copied().filter(|&b| base64_value(b).is_some() || b == b'='));
+            let usable_len = self.carry.len() - self.carry.len() % 4;
+            if usable_len > 0 {
+                let group: Vec<u8> = self.carry.drain(..usable_len).collect();
+                self.pending.extend(decode_base64_groups(&group));
+            }
+        }
+
+        let n = self.pending.len().min(buf.len());
+        for slot in buf.iter_mut().take(n) {
+            *slot = self.pending.pop_front().unwrap();
+        }
+        Ok(n)
+    }
+}
+
+// Decodes one chunk of quoted-printable data, translating `=XX` hex escapes
+// to bytes and dropping soft line breaks (`=` immediately followed by a
+// line ending). Returns the decoded bytes plus any trailing `=`/`=X` bytes
+// that couldn't be resolved without more input, for the caller to carry
+// over to the next chunk.
+fn decode_quoted_printable_chunk(data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut output = Vec::with_capacity(data.len());
+    let mut i = 0;
+    while i < data.len() {
+        if data[i] != b'=' {
+            output.push(data[i]);
+            i += 1;
+            continue;
+        }
+        if i + 1 >= data.len() {
+            return (output, data[i..].to_vec());
+        }
+        if data[i + 1] == b'\n' {
+            i += 2; // soft line break (bare LF)
+            continue;
+        }
+        if i + 2 >= data.len() {
+            return (output, data[i..].to_vec());
+        }
+        if data[i + 1] == b'\r' && data[i + 2] == b'\n' {
+            i += 3; // soft line break (CRLF)
+            continue;
+        }
+        if data[i + 1].is_ascii_hexdigit() && data[i + 2].is_ascii_hexdigit() {
+            let hex = std::str::from_utf8(&data[i + 1..i + 3]).unwrap();
+            output.push(u8::from_str_radix(hex, 16).unwrap_or(b'?'));
+            i += 3;
+            continue;
+        }
+        // Not a recognized escape; emit the '=' literally.
+        output.push(b'=');
+        i += 1;
+    }
+    (output, Vec::new())
+}
+
+struct QuotedPrintableDecodeReader<R: Read> {
+    inner: R,
+    carry: Vec<u8>,
+    pending: std::collections::VecDeque<u8>,
+    inner_eof: bool,
+}
+
+impl<R: Read> QuotedPrintableDecodeReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            carry: Vec::new(),
+            pending: std::collections::VecDeque::new(),
+            inner_eof: false,
+        }
+    }
+}
+
+impl<R: Read> Read for QuotedPrintableDecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pending.is_empty() && !self.inner_eof {
+            let mut raw = [0u8; CHUNK_SIZE];
+            let n = self.inner.read(&mut raw)?;
+            if n == 0 {
+                self.inner_eof = true;
+                if !self.carry.is_empty() {
+                    self.pending.extend(std::mem::take(&mut self.carry));
+                }
+                break;
+            }
+            let mut data = std::mem::take(&mut self.carry);
+            data.extend_from_slice(&raw[..n]);
+            let (decoded, carry) = decode_quoted_printable_chunk(&data);
+            self.carry = carry;
+            self.pending.extend(decoded);
+        }
+
+        let n = self.pending.len().min(buf.len());
+        for slot in buf.iter_mut().take(n) {
+            *slot = self.pending.pop_front().unwrap();
+        }
+        Ok(n)
+    }
+}
+
+// Strips HTTP `Transfer-Encoding: chunked` framing from a raw captured
+// response body: each chunk is a hex size line (optionally followed by
+// `;`-delimited extensions, which are ignored) terminated by CRLF, that many
+// bytes of chunk data, and a trailing CRLF; the body ends at a zero-length
+// chunk. `remaining_in_chunk` tracks how many chunk-data bytes are still
+// owed before the next size line is expected, mirroring the carry-state
+// pattern used by the other streaming decoders above.
+struct HttpChunkedDecodeReader<R: Read> {
+    inner: BufReader<R>,
+    remaining_in_chunk: Option<usize>,
+    end: bool,
+}
+
+fn chunked_truncation_error(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, message.to_string())
+}
+
+impl<R: Read> HttpChunkedDecodeReader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner: BufReader::new(inner),
+            remaining_in_chunk: None,
+            end: false,
+        }
+    }
+
+    fn read_chunk_size(&mut self) -> std::io::Result<usize> {
+        let mut line = String::new();
+        let bytes_read = self.inner.read_line(&mut line)?;
+        if bytes_read == 0 {
+            return Err(chunked_truncation_error(
+                "truncated chunked body: missing chunk-size line",
+            ));
+        }
+        let size_part = line.trim_end_matches(['\r', '\n']);
+        let size_part = size_part.split(';').next().unwrap_or("").trim();
+        usize::from_str_radix(size_part, 16)
+            .map_err(|_| chunked_truncation_error(&format!("invalid chunk-size line: {:?}", line)))
+    }
+
+    fn consume_chunk_terminator(&mut self) -> std::io::Result<()> {
+        let mut crlf = [0u8; 2];
+        self.inner
+            .read_exact(&mut crlf)
+            .map_err(|_| chunked_truncation_error("truncated chunked body: missing chunk terminator"))
+    }
+}
+
+impl<R: Read> Read for HttpChunkedDecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.end {
+            return Ok(0);
+        }
+
+        loop {
+            match self.remaining_in_chunk {
+                None => {
+                    let size = self.read_chunk_size()?;
+                    if size == 0 {
+                        // Trailing CRLF after the terminating zero-length chunk
+                        // (MIME trailer headers, if any, are not supported).
+                        let mut trailer = String::new();
+                        self.inner.read_line(&mut trailer)?;
+                        self.end = true;
+                        return Ok(0);
+                    }
+                    self.remaining_in_chunk = Some(size);
+                }
+                Some(0) => {
+                    self.consume_chunk_terminator()?;
+                    self.remaining_in_chunk = None;
+                }
+                Some(remaining) => {
+                    let to_read = remaining.min(buf.len());
+                    let bytes_read = self.inner.read(&mut buf[..to_read])?;
+                    if bytes_read == 0 {
+                        return Err(chunked_truncation_error(
+                            "truncated chunked body: unexpected EOF in chunk data",
+                        ));
+                    }
+                    self.remaining_in_chunk = Some(remaining - bytes_read);
+                    return Ok(bytes_read);
+                }
+            }
+        }
+    }
+}
+
+// A byte stream that transparently strips HTTP chunked-transfer framing,
+// decompresses, and/or un-transfer-encodes its input, so the rest of the
+// pipeline can keep treating the file as a plain byte stream. `Plain` is
+// the only variant that also implements `Seek`: once bytes flow through a
+// decoder they generally can't be seeked into at arbitrary offsets without
+// a full index, so callers that need strategic head/middle/tail sampling
+// (see `prepare_sample`) fall back to a single sequential read instead.
+enum InputReader {
+    Plain(BufReader<File>),
+    Wrapped(Box<dyn Read>),
+}
+
+impl Read for InputReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            InputReader::Plain(r) => r.read(buf),
+            InputReader::Wrapped(r) => r.read(buf),
+        }
+    }
+}
+
+// Reads the first few bytes of `input` to sniff its compression, then puts
+// them back in front of the stream (seeking back to the start for `Plain`,
+// or re-chaining them in front of a `Wrapped` reader that can't be
+// seeked).
+fn sniff_compression_header(input: &mut InputReader) -> PyResult<Compression> {
+    let mut header = [0u8; 4];
+    let n = input
+        .read(&mut header)
+        .map_err(|e| PyIOError::new_err(format!("Failed to read file header: {}", e)))?;
+    let resolved = sniff_compression(&header[..n]);
+
+    match input {
+        InputReader::Plain(r) => {
+            r.seek(SeekFrom::Start(0))
+                .map_err(|e| PyIOError::new_err(format!("Failed to rewind file: {}", e)))?;
+        }
+        InputReader::Wrapped(r) => {
+            let prefix = header[..n].to_vec();
+            let rest = std::mem::replace(r, Box::new(std::io::empty()));
+            *r = Box::new(std::io::Cursor::new(prefix).chain(rest));
+        }
+    }
+
+    Ok(resolved)
+}
+
+// Opens `file_path`, determines its compression (explicit override or
+// magic-byte sniffing) and transfer encoding, and returns a reader that
+// yields the fully decoded byte stream, along with the compression that
+// was used. When `http_chunked` is set, HTTP `Transfer-Encoding: chunked`
+// framing is stripped first, before compression/transfer-encoding
+// decoding are applied to the dechunked body.
+fn open_decoded(
+    file_path: &str,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<(InputReader, Compression)> {
+    let path = Path::new(file_path);
     let file =
         File::open(path).map_err(|e| PyIOError::new_err(format!("Failed to open file: {}", e)))?;
+    let reader = BufReader::new(file);
+
+    let mut input = if http_chunked {
+        InputReader::Wrapped(Box::new(HttpChunkedDecodeReader::new(reader)))
+    } else {
+        InputReader::Plain(reader)
+    };
+
+    let resolved_compression = match compression {
+        Some(explicit) => parse_compression(explicit)?,
+        None => sniff_compression_header(&mut input)?,
+    };
+
+    let resolved_transfer_encoding = transfer_encoding.map(parse_transfer_encoding).transpose()?;
+
+    input = match resolved_compression {
+        Compression::None => input,
+        Compression::Gzip => InputReader::Wrapped(Box::new(GzDecoder::new(input))),
+        Compression::Zstd => InputReader::Wrapped(Box::new(
+            ZstdStreamingDecoder::new(input)
+                .map_err(|e| PyIOError::new_err(format!("Failed to open zstd stream: {}", e)))?,
+        )),
+    };
+
+    input = match resolved_transfer_encoding {
+        None | Some(TransferEncoding::None) => input,
+        Some(TransferEncoding::Base64) => InputReader::Wrapped(Box::new(Base64DecodeReader::new(input))),
+        Some(TransferEncoding::QuotedPrintable) => {
+            InputReader::Wrapped(Box::new(QuotedPrintableDecodeReader::new(input)))
+        }
+    };
+
+    Ok((input, resolved_compression))
+}
+
+// Reads up to `sample_size` bytes sequentially from a (possibly
+// decompressed) stream. Used in place of `read_strategic_sample`'s
+// head/middle/tail distribution when the input is compressed, since
+// compressed streams can't be seeked into at arbitrary offsets.
+fn read_sequential_sample(
+    reader: &mut InputReader,
+    sample_size: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut buffer = vec![0u8; sample_size];
+    let mut total_read = 0;
+    while total_read < sample_size {
+        let bytes_read = reader.read(&mut buffer[total_read..])?;
+        if bytes_read == 0 {
+            break;
+        }
+        total_read += bytes_read;
+    }
+    buffer.truncate(total_read);
+    Ok(buffer)
+}
 
-    // Get file size
-    let metadata = file
-        .metadata()
+// Reads the strategic sample for a file and runs BOM/byte-pattern pre-
+// detection, producing everything the scoring loop needs. Shared by
+// `analyse_from_path_stream` and `analyse_candidates_from_path_stream` so the
+// two entry points can never disagree on what sample was scored.
+fn prepare_sample(
+    file_path: &str,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<(Vec<u8>, &'static str, &'static str, usize, Vec<&'static str>, Vec<&'static str>)> {
+    let path = Path::new(file_path);
+    let metadata = std::fs::metadata(path)
         .map_err(|e| PyIOError::new_err(format!("Failed to get file metadata: {}", e)))?;
     let file_size = metadata.len();
 
@@ -366,7 +1019,10 @@ fn analyse_from_path_stream(
         return Err(PyIOError::new_err("File is empty"));
     }
 
-    // Calculate effective sample size
+    // Calculate effective sample size. For compressed inputs this is sized
+    // off the on-disk (compressed) length, which under-estimates the
+    // decompressed content but is the only size known up front without a
+    // full decompression pass.
     let sample_size = calculate_sample_size(
         file_size,
         min_sample_size,
@@ -374,11 +1030,19 @@ fn analyse_from_path_stream(
         max_sample_size,
     );
 
-    let mut reader = BufReader::new(file);
-
-    // Read strategic sample from file
-    let buffer = read_strategic_sample(&mut reader, file_size, sample_size)
-        .map_err(|e| PyIOError::new_err(format!("Failed to read file: {}", e)))?;
+    let (mut input, _resolved_compression) =
+        open_decoded(file_path, compression, transfer_encoding, http_chunked)?;
+
+    // Strategic head/middle/tail sampling needs to seek the raw file, which
+    // isn't possible once the bytes are flowing through a decompressor or
+    // transfer-encoding decoder, so those inputs fall back to a single
+    // sequential read from the start of the decoded stream.
+    let buffer = match &mut input {
+        InputReader::Plain(plain) => read_strategic_sample(plain, file_size, sample_size)
+            .map_err(|e| PyIOError::new_err(format!("Failed to read file: {}", e)))?,
+        InputReader::Wrapped(_) => read_sequential_sample(&mut input, sample_size)
+            .map_err(|e| PyIOError::new_err(format!("Failed to read file: {}", e)))?,
+    };
 
     if buffer.is_empty() {
         return Err(PyIOError::new_err("Failed to read any data from file"));
@@ -386,23 +1050,40 @@ fn analyse_from_path_stream(
 
     // Detect newline style
     let newlines = detect_newline_style(&buffer);
+    let (encoding_str, skip_bytes, encodings_to_try, byte_hints) = detect_encoding_candidates(&buffer);
 
-    // Detect encoding (reuse existing logic)
+    Ok((buffer, newlines, encoding_str, skip_bytes, encodings_to_try, byte_hints))
+}
+
+// Core of the detection pipeline, shared between file-backed sampling
+// (`prepare_sample`) and the in-memory `analyse_bytes` entry point: picks an
+// initial best-guess encoding (BOM / UTF-16 null-pattern / escape-sequence
+// pre-filters, falling back to chardet) and builds the ranked list of
+// encodings `score_candidates` should try.
+fn detect_encoding_candidates(
+    buffer: &[u8],
+) -> (&'static str, usize, Vec<&'static str>, Vec<&'static str>) {
+    // The 4-byte UTF-32LE BOM (`FF FE 00 00`) starts with the same two bytes
+    // as the 2-byte UTF-16LE BOM, so the UTF-32 checks must come first or
+    // every UTF-32LE-BOM buffer matches UTF-16LE instead and the UTF-32LE
+    // branch below is unreachable.
     let (encoding_str, skip_bytes) = if buffer.starts_with(&[0xEF, 0xBB, 0xBF]) {
         ("utf_8", 3)
-    } else if buffer.starts_with(&[0xFF, 0xFE]) {
-        ("UTF-16LE", 2)
-    } else if buffer.starts_with(&[0xFE, 0xFF]) {
-        ("UTF-16BE", 2)
     } else if buffer.starts_with(&[0xFF, 0xFE, 0x00, 0x00]) {
         ("UTF-32LE", 4)
     } else if buffer.starts_with(&[0x00, 0x00, 0xFE, 0xFF]) {
         ("UTF-32BE", 4)
-    } else if let Some(utf16_encoding) = detect_utf16_pattern(&buffer) {
+    } else if buffer.starts_with(&[0xFF, 0xFE]) {
+        ("UTF-16LE", 2)
+    } else if buffer.starts_with(&[0xFE, 0xFF]) {
+        ("UTF-16BE", 2)
+    } else if let Some(utf16_encoding) = detect_utf16_pattern(buffer) {
         (utf16_encoding, 0)
+    } else if let Some(escape_encoding) = detect_escape_based_encoding(buffer) {
+        (escape_encoding, 0)
     } else {
-        let byte_hints = analyze_byte_patterns(&buffer);
-        let result = chardet::detect(&buffer);
+        let byte_hints = analyze_byte_patterns(buffer);
+        let result = chardet::detect(buffer);
         let detected = result.0.to_lowercase().replace("-", "_");
 
         let encoding = match detected.as_str() {
@@ -440,10 +1121,9 @@ fn analyse_from_path_stream(
         (encoding, 0)
     };
 
-    let buffer_slice = &buffer[skip_bytes..];
     let mut encodings_to_try = vec![encoding_str];
 
-    let byte_hints = analyze_byte_patterns(&buffer);
+    let byte_hints = analyze_byte_patterns(buffer);
 
     for enc in &[
         "UTF-8",
@@ -470,76 +1150,141 @@ fn analyse_from_path_stream(
         }
     }
 
-    let mut best_encoding = None;
-    let mut min_error_ratio = 1.0;
-    let mut best_score = f32::MIN;
+    (encoding_str, skip_bytes, encodings_to_try, byte_hints)
+}
 
-    for encoding_name in &encodings_to_try {
+// Scores every candidate encoding against the sample and returns them
+// ranked best-first as (encoding_rs canonical name, score, error_ratio).
+fn score_candidates(
+    buffer_slice: &[u8],
+    preferred_encoding: &str,
+    encodings_to_try: &[&str],
+    byte_hints: &[&str],
+    locale_family: Option<LocaleFamily>,
+) -> Vec<(String, i64, f32)> {
+    let mut candidates = Vec::new();
+
+    for encoding_name in encodings_to_try {
         if let Some(encoding) = encoding_rs::Encoding::for_label(encoding_name.as_bytes()) {
-            let (decoded, _, had_errors) = encoding.decode(buffer_slice);
+            let (decoded, _, _had_errors) = encoding.decode(buffer_slice);
 
             let error_chars = decoded.chars().filter(|&c| c == '\u{FFFD}').count();
             let total_chars = decoded.chars().count().max(1);
             let error_ratio = error_chars as f32 / total_chars as f32;
 
-            let mut score = 1.0 - error_ratio;
-
-            if encoding_name == &encoding_str {
-                score += 0.05;
-            }
-
-            let lang_hints = detect_language_hints(&decoded);
-
-            if lang_hints.contains(&"arabic") && encoding_name.contains("1256") {
-                score += 0.5;
-            }
-            if lang_hints.contains(&"turkish") && encoding_name.contains("1254") {
-                score += 0.4;
-            }
-            if lang_hints.contains(&"korean") {
-                if encoding_name.contains("949") || encoding_name.contains("windows-949") {
-                    score += 0.4;
-                } else if encoding_name.contains("euc-kr") || encoding_name.contains("EUC-KR") {
-                    score += 0.2;
-                }
-            }
-            if lang_hints.contains(&"cyrillic") {
-                if encoding_name.contains("mac-cyrillic")
-                    || encoding_name.contains("x-mac-cyrillic")
-                {
-                    score += 0.5;
-                } else if encoding_name.contains("1251") {
-                    score += 0.2;
-                }
-            }
+            // Normalize the raw transition score by sample length (per 1000
+            // chars) so it stays comparable across wildly different sample
+            // sizes, and so the fixed locale/byte-hint biases below keep a
+            // meaningful weight instead of being swamped by a large sample's
+            // raw transition total.
+            let mut score =
+                (score_char_transitions(&decoded) as f64 * 1000.0 / total_chars as f64) as i64;
 
-            if lang_hints.contains(&"arabic") && encoding_name.contains("1251") {
-                score -= 0.5;
-            }
-            if lang_hints.contains(&"cyrillic") && encoding_name.contains("1256") {
-                score -= 0.9;
+            if *encoding_name == preferred_encoding {
+                score += 10;
             }
 
             if byte_hints.contains(&"likely_mac_cyrillic")
                 && (encoding_name.contains("mac-cyrillic")
                     || encoding_name.contains("x-mac-cyrillic"))
             {
-                score += 0.4;
+                score += 80;
             }
 
-            if score > best_score || (score == best_score && error_ratio < min_error_ratio) {
-                best_score = score;
-                min_error_ratio = error_ratio;
-                best_encoding = Some(encoding.name().to_string());
-
-                if !had_errors && error_ratio == 0.0 && score > 1.0 {
-                    break;
+            if let Some(family) = locale_family {
+                // `preferred_labels()` is all lowercase, but `encoding_name`
+                // (and some `encodings_to_try` entries, e.g. "KOI8-R") are
+                // not, so compare case-insensitively or the bonus/penalty
+                // below silently never fires for those families.
+                let encoding_name_lower = encoding_name.to_lowercase();
+                let matches_family = family
+                    .preferred_labels()
+                    .iter()
+                    .any(|l| encoding_name_lower.contains(l));
+                if matches_family {
+                    score += 150;
+                } else if encodings_to_try
+                    .iter()
+                    .any(|l| family.preferred_labels().contains(&l.to_lowercase().as_str()))
+                    && *encoding_name != "UTF-8"
+                    && *encoding_name != "ISO-8859-1"
+                {
+                    score -= 60;
                 }
             }
+
+            candidates.push((encoding.name().to_string(), score, error_ratio));
         }
     }
 
-    let mut final_encoding = best_encoding.unwrap_or_else(|| "UTF-8".to_string());
+    candidates.sort_by(|a, b| {
+        b.1.cmp(&a.1)
+            .then(a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    candidates
+}
+
+// Normalizes a raw transition score into a 0.0-1.0 confidence via a
+// logistic squash, so callers get a stable range regardless of how large the
+// underlying bonus/penalty constants are.
+fn score_to_confidence(score: i64) -> f32 {
+    1.0 / (1.0 + (-(score as f32) / 300.0).exp())
+}
+
+// Shared tail of the detection pipeline: given a sample that's already had
+// its encoding candidates worked out by `detect_encoding_candidates`, scores
+// them and builds the final `AnalysisResult`. Used by both
+// `analyse_from_path_stream` (sample read from disk) and `analyse_bytes`
+// (sample already in memory) so the two entry points can never drift apart.
+fn finalize_analysis(
+    buffer: &[u8],
+    newlines: &str,
+    encoding_str: &'static str,
+    skip_bytes: usize,
+    encodings_to_try: &[&'static str],
+    byte_hints: &[&'static str],
+    locale_family: Option<LocaleFamily>,
+) -> AnalysisResult {
+    // HZ-GB2312 has no `encoding_rs` codec, so it can never survive
+    // `score_candidates`' decode-based scoring; trust the escape-marker
+    // detection directly, as the request asks for "return it directly".
+    if encoding_str == "HZ-GB2312" {
+        return AnalysisResult {
+            encoding: normalize_encoding_name(encoding_str),
+            newlines: newlines.to_string(),
+            confidence: 1.0,
+        };
+    }
+
+    // Same reasoning for UTF-32LE/BE: `encoding_rs` has no codec for either,
+    // so `score_candidates`' `Encoding::for_label` lookup would silently
+    // skip the candidate and let UTF-8/windows-1252 win instead, even
+    // though the BOM pre-filter in `detect_encoding_candidates` already
+    // identified it with certainty.
+    if encoding_str == "UTF-32LE" || encoding_str == "UTF-32BE" {
+        return AnalysisResult {
+            encoding: normalize_encoding_name(encoding_str),
+            newlines: newlines.to_string(),
+            confidence: 1.0,
+        };
+    }
+
+    let buffer_slice = &buffer[skip_bytes..];
+
+    let candidates = score_candidates(
+        buffer_slice,
+        encoding_str,
+        encodings_to_try,
+        byte_hints,
+        locale_family,
+    );
+
+    let (best_encoding, best_score) = candidates
+        .first()
+        .map(|(name, score, _)| (name.clone(), *score))
+        .unwrap_or_else(|| ("UTF-8".to_string(), 0));
+
+    let mut final_encoding = best_encoding;
 
     if final_encoding.to_lowercase().contains("euc-kr")
         || final_encoding.to_lowercase().contains("euc_kr")
@@ -547,22 +1292,261 @@ fn analyse_from_path_stream(
         final_encoding = "windows-949".to_string();
     }
 
-    let normalized_encoding = normalize_encoding_name(&final_encoding);
-
-    Ok(AnalysisResult {
-        encoding: normalized_encoding,
+    AnalysisResult {
+        encoding: normalize_encoding_name(&final_encoding),
         newlines: newlines.to_string(),
-    })
+        confidence: score_to_confidence(best_score),
+    }
 }
 
-// Helper function to get encoding_rs::Encoding from encoding name
-// Note: This maps Python/user-facing encoding names to encoding_rs labels.
-// This is separate from normalize_encoding_name which converts TO Python-compatible names.
-// Here we convert FROM user input TO encoding_rs labels (e.g., "utf-8", "windows-1252").
-fn get_encoding_rs(encoding_name: &str) -> Option<&'static encoding_rs::Encoding> {
-    let normalized = encoding_name.to_lowercase().replace("-", "_");
-
-    let label = match normalized.as_str() {
+/// Analyzes encoding and newline style from a file using streaming
+///
+/// `locale` (or a TLD like `".ru"`) is an optional hint about the content's
+/// origin; when it maps to a known legacy-encoding family, candidates from
+/// that family are biased towards during scoring. This resolves the common
+/// windows-1251-vs-windows-1256-vs-mac-cyrillic ambiguity that byte-pattern
+/// heuristics alone cannot, by letting the caller supply prior knowledge.
+/// `compression` selects `"gzip"`/`"zstd"` decompression of the input before
+/// analysis; left as `None`, the compression (if any) is sniffed from the
+/// file's leading magic bytes. `transfer_encoding` additionally unwraps a
+/// MIME Content-Transfer-Encoding (`"base64"` or `"quoted-printable"`)
+/// before analysis, for inspecting raw MIME part bodies directly.
+/// `http_chunked` strips HTTP `Transfer-Encoding: chunked` framing before
+/// any of the above, so a raw captured HTTP response body can be analysed
+/// without a separate dechunking step.
+#[pyfunction]
+#[pyo3(signature = (file_path, min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None, locale=None, compression=None, transfer_encoding=None, http_chunked=false))]
+fn analyse_from_path_stream(
+    file_path: String,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    locale: Option<String>,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<AnalysisResult> {
+    let (buffer, newlines, encoding_str, skip_bytes, encodings_to_try, byte_hints) = prepare_sample(
+        &file_path,
+        min_sample_size,
+        percentage_sample_size,
+        max_sample_size,
+        compression,
+        transfer_encoding,
+        http_chunked,
+    )?;
+
+    let locale_family = locale.as_deref().and_then(locale_hint_family);
+
+    Ok(finalize_analysis(
+        &buffer,
+        newlines,
+        encoding_str,
+        skip_bytes,
+        &encodings_to_try,
+        &byte_hints,
+        locale_family,
+    ))
+}
+
+/// Analyzes encoding and newline style from an in-memory buffer, running the
+/// same detection pipeline as `analyse_from_path_stream` for callers that
+/// already have the bytes in hand (an HTTP response body, a subtitle blob
+/// read by the caller) rather than a path on disk.
+///
+/// `max_sample_size` caps how much of `data` is analyzed; left as `None`,
+/// the whole buffer is used.
+#[pyfunction]
+#[pyo3(signature = (data, max_sample_size=None))]
+fn analyse_bytes(data: &[u8], max_sample_size: Option<usize>) -> PyResult<AnalysisResult> {
+    if data.is_empty() {
+        return Err(PyIOError::new_err("data is empty"));
+    }
+
+    let buffer = match max_sample_size {
+        Some(max) if data.len() > max => &data[..max],
+        _ => data,
+    };
+
+    let newlines = detect_newline_style(buffer);
+    let (encoding_str, skip_bytes, encodings_to_try, byte_hints) = detect_encoding_candidates(buffer);
+
+    Ok(finalize_analysis(
+        buffer,
+        newlines,
+        encoding_str,
+        skip_bytes,
+        &encodings_to_try,
+        &byte_hints,
+        None,
+    ))
+}
+
+/// Returns the top `top_n` encoding candidates considered for `file_path`,
+/// ranked best-first, as `(encoding, confidence, error_ratio)` tuples. Unlike
+/// `analyse_from_path_stream`, which only surfaces the winner, this lets
+/// callers implement their own tie-breaking or present ambiguous results to
+/// a user instead of being locked into this crate's internal preference
+/// order.
+#[pyfunction]
+#[pyo3(signature = (file_path, min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None, locale=None, top_n=5, compression=None, transfer_encoding=None, http_chunked=false))]
+fn analyse_candidates_from_path_stream(
+    file_path: String,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    locale: Option<String>,
+    top_n: usize,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<Vec<(String, f32, f32)>> {
+    Ok(ranked_candidates(
+        &file_path,
+        min_sample_size,
+        percentage_sample_size,
+        max_sample_size,
+        locale,
+        top_n,
+        compression,
+        transfer_encoding,
+        http_chunked,
+    )?
+    .into_iter()
+    .map(|c| (c.encoding, c.confidence, c.error_ratio))
+    .collect())
+}
+
+/// A single ranked encoding guess from `analyse_candidates`, pairing the
+/// encoding label with its normalized confidence and replacement-character
+/// ratio over the sample.
+#[pyclass]
+#[derive(Clone)]
+struct AnalysisCandidate {
+    #[pyo3(get)]
+    encoding: String,
+    #[pyo3(get)]
+    confidence: f32,
+    #[pyo3(get)]
+    error_ratio: f32,
+}
+
+#[pymethods]
+impl AnalysisCandidate {
+    fn __repr__(&self) -> PyResult<String> {
+        Ok(format!(
+            "AnalysisCandidate(encoding='{}', confidence={}, error_ratio={})",
+            self.encoding, self.confidence, self.error_ratio
+        ))
+    }
+}
+
+// Shared core behind both `analyse_candidates_from_path_stream` (tuples, for
+// backwards compatibility) and `analyse_candidates` (pyclass instances).
+fn ranked_candidates(
+    file_path: &str,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    locale: Option<String>,
+    top_n: usize,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<Vec<AnalysisCandidate>> {
+    let (buffer, _newlines, encoding_str, skip_bytes, encodings_to_try, byte_hints) = prepare_sample(
+        file_path,
+        min_sample_size,
+        percentage_sample_size,
+        max_sample_size,
+        compression,
+        transfer_encoding,
+        http_chunked,
+    )?;
+
+    // See finalize_analysis: HZ-GB2312 can't be scored via
+    // score_candidates since encoding_rs has no codec for it.
+    if encoding_str == "HZ-GB2312" {
+        return Ok(vec![AnalysisCandidate {
+            encoding: normalize_encoding_name(encoding_str),
+            confidence: 1.0,
+            error_ratio: 0.0,
+        }]);
+    }
+
+    // Same reasoning for UTF-32LE/BE: encoding_rs has no codec for either,
+    // so they'd otherwise be silently dropped from the scored candidates.
+    if encoding_str == "UTF-32LE" || encoding_str == "UTF-32BE" {
+        return Ok(vec![AnalysisCandidate {
+            encoding: normalize_encoding_name(encoding_str),
+            confidence: 1.0,
+            error_ratio: 0.0,
+        }]);
+    }
+
+    let buffer_slice = &buffer[skip_bytes..];
+    let locale_family = locale.as_deref().and_then(locale_hint_family);
+
+    let candidates = score_candidates(
+        buffer_slice,
+        encoding_str,
+        &encodings_to_try,
+        &byte_hints,
+        locale_family,
+    );
+
+    Ok(candidates
+        .into_iter()
+        .take(top_n)
+        .map(|(name, score, error_ratio)| AnalysisCandidate {
+            encoding: normalize_encoding_name(&name),
+            confidence: score_to_confidence(score),
+            error_ratio,
+        })
+        .collect())
+}
+
+/// Returns the top `top_n` encoding candidates considered for `file_path`,
+/// ranked best-first, as `AnalysisCandidate` instances. This is the same ranking
+/// `analyse_candidates_from_path_stream` exposes as plain tuples; the
+/// pyclass form gives callers named fields (`.encoding`, `.confidence`,
+/// `.error_ratio`) instead of having to remember tuple positions, letting
+/// downstream tools implement their own tie-breaking or present ambiguous
+/// results to a user.
+#[pyfunction]
+#[pyo3(signature = (file_path, min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None, locale=None, top_n=5, compression=None, transfer_encoding=None, http_chunked=false))]
+fn analyse_candidates(
+    file_path: String,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    locale: Option<String>,
+    top_n: usize,
+    compression: Option<&str>,
+    transfer_encoding: Option<&str>,
+    http_chunked: bool,
+) -> PyResult<Vec<AnalysisCandidate>> {
+    ranked_candidates(
+        &file_path,
+        min_sample_size,
+        percentage_sample_size,
+        max_sample_size,
+        locale,
+        top_n,
+        compression,
+        transfer_encoding,
+        http_chunked,
+    )
+}
+
+// Helper function to get encoding_rs::Encoding from encoding name
+// Note: This maps Python/user-facing encoding names to encoding_rs labels.
+// This is separate from normalize_encoding_name which converts TO Python-compatible names.
+// Here we convert FROM user input TO encoding_rs labels (e.g., "utf-8", "windows-1252").
+fn get_encoding_rs(encoding_name: &str) -> Option<&'static encoding_rs::Encoding> {
+    let normalized = encoding_name.to_lowercase().replace("-", "_");
+
+    let label = match normalized.as_str() {
         "utf_8" | "utf8" => "utf-8",
         "utf_16" | "utf16" => "utf-16",
         "utf_16_le" | "utf16_le" | "utf_16le" | "utf16le" => "utf-16le",
@@ -586,18 +1570,325 @@ fn get_encoding_rs(encoding_name: &str) -> Option<&'static encoding_rs::Encoding
         "mac_cyrillic" | "x_mac_cyrillic" => "x-mac-cyrillic",
         "koi8_r" | "koi8r" => "koi8-r",
         "koi8_u" | "koi8u" => "koi8-u",
+        "iso_2022_jp" | "iso2022jp" => "iso-2022-jp",
+        // HZ-GB2312 has no encoding_rs codec (it's not in the WHATWG
+        // encoding spec; `for_label` would map "hz-gb-2312" to the
+        // replacement encoding), so this resolves to None;
+        // normalize_file_stream can detect it but can't transcode it.
+        "hz_gb_2312" | "hz_gb2312" | "hzgb2312" => return None,
         other => other,
     };
 
     encoding_rs::Encoding::for_label(label.as_bytes())
 }
 
+// Which Unicode normalization form (if any) `normalize_file_stream` should
+// apply to decoded text before re-encoding it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NormalizationForm {
+    Nfc,
+    Nfd,
+    Nfkc,
+    Nfkd,
+}
+
+fn parse_normalization_form(value: &str) -> PyResult<NormalizationForm> {
+    match value.to_uppercase().as_str() {
+        "NFC" => Ok(NormalizationForm::Nfc),
+        "NFD" => Ok(NormalizationForm::Nfd),
+        "NFKC" => Ok(NormalizationForm::Nfkc),
+        "NFKD" => Ok(NormalizationForm::Nfkd),
+        _ => Err(PyIOError::new_err(format!(
+            "Invalid target_normalization '{}'. Must be one of 'NFC', 'NFD', 'NFKC', 'NFKD'",
+            value
+        ))),
+    }
+}
+
+fn normalize_form(form: NormalizationForm, text: &str) -> String {
+    match form {
+        NormalizationForm::Nfc => text.nfc().collect(),
+        NormalizationForm::Nfd => text.nfd().collect(),
+        NormalizationForm::Nfkc => text.nfkc().collect(),
+        NormalizationForm::Nfkd => text.nfkd().collect(),
+    }
+}
+
+// Hangul conjoining jamo (Leading U+1100-1112, Vowel U+1161-1175, Trailing
+// U+11A8-11C2) and precomposed syllables without a trailing consonant are
+// all starters (canonical combining class 0), yet NFC can still compose a
+// starter with the *next* starter that follows it (L+V -> an LV syllable,
+// LV+T -> an LVT syllable). `stable_prefix_boundary` must not treat one of
+// these as a safe cut point, or a chunk boundary landing between an L and
+// a following V would normalize them separately and lose the composition.
+fn is_unstable_hangul_starter(ch: char) -> bool {
+    let code = ch as u32;
+    matches!(code, 0x1100..=0x1112 | 0x1161..=0x1175 | 0x11A8..=0x11C2)
+        || ((0xAC00..=0xD7A3).contains(&code) && (code - 0xAC00) % 28 == 0)
+}
+
+// Finds the byte offset where the trailing combining-character run starts:
+// everything from the last "starter" (canonical combining class 0) to the
+// end of the string must be held back across a chunk boundary, since a
+// future chunk could still append more combining marks onto it. Returns 0
+// (hold back everything) when no starter is found in `text`. Additionally
+// walks back past any chain of Hangul conjoining jamo / trailing-consonant-
+// less syllables (see `is_unstable_hangul_starter`), since those starters
+// can still compose with each other across the boundary.
+fn stable_prefix_boundary(text: &str) -> usize {
+    let mut boundary = 0;
+    let mut found_starter = false;
+    for (idx, ch) in text.char_indices().rev() {
+        if canonical_combining_class(ch) != 0 {
+            continue;
+        }
+        if !found_starter {
+            boundary = idx;
+            found_starter = true;
+            if !is_unstable_hangul_starter(ch) {
+                break;
+            }
+            continue;
+        }
+        if is_unstable_hangul_starter(ch) {
+            boundary = idx;
+            continue;
+        }
+        break;
+    }
+    boundary
+}
+
+// Appends `text` to the carried-over tail from the previous chunk and
+// normalizes whatever prefix is now guaranteed stable, holding back the
+// rest (a possibly-incomplete combining sequence) for the next call. On the
+// final chunk everything is flushed and normalized.
+fn apply_normalization(
+    form: Option<NormalizationForm>,
+    carry: &mut String,
+    text: &str,
+    is_last: bool,
+) -> String {
+    let Some(form) = form else {
+        return text.to_string();
+    };
+
+    carry.push_str(text);
+
+    if is_last {
+        let normalized = normalize_form(form, carry);
+        carry.clear();
+        return normalized;
+    }
+
+    let boundary = stable_prefix_boundary(carry);
+    let stable = normalize_form(form, &carry[..boundary]);
+    *carry = carry[boundary..].to_string();
+    stable
+}
+
+// `encoding_rs` has no UTF-32 support, but `analyse_from_path_stream` can
+// detect UTF-32LE/BE from the BOM, so `normalize_file_stream` needs a small
+// self-contained codec to use it as a source or target encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Utf32Endianness {
+    Little,
+    Big,
+}
+
+struct Utf32Decoder {
+    endianness: Utf32Endianness,
+    pending: Vec<u8>,
+    // Whether the next code unit decoded would be the very first one for
+    // this stream; used to strip a leading U+FEFF the same way
+    // `encoding_rs`'s UTF-16 `new_decoder()` strips its BOM, since the
+    // caller feeds the raw file (including any BOM) from offset 0.
+    at_start: bool,
+}
+
+impl Utf32Decoder {
+    fn new(endianness: Utf32Endianness) -> Self {
+        Self {
+            endianness,
+            pending: Vec::new(),
+            at_start: true,
+        }
+    }
+
+    // Reads 4-byte code units in the detected endianness, buffering a
+    // partial trailing group across chunk boundaries. Values above
+    // U+10FFFF and surrogate-range scalars are invalid UTF-32 and are
+    // replaced with U+FFFD rather than rejected outright.
+    fn decode_to_string(&mut self, src: &[u8], dst: &mut String, is_last: bool) -> bool {
+        let mut buf = std::mem::take(&mut self.pending);
+        buf.extend_from_slice(src);
+        let mut had_errors = false;
+
+        let mut chunks = buf.chunks_exact(4);
+        for group in &mut chunks {
+            let code = match self.endianness {
+                Utf32Endianness::Little => {
+                    u32::from_le_bytes([group[0], group[1], group[2], group[3]])
+                }
+                Utf32Endianness::Big => {
+                    u32::from_be_bytes([group[0], group[1], group[2], group[3]])
+                }
+            };
+            if self.at_start {
+                self.at_start = false;
+                if code == 0xFEFF {
+                    continue;
+                }
+            }
+            let is_surrogate = (0xD800..=0xDFFF).contains(&code);
+            let ch = if is_surrogate || code > 0x10FFFF {
+                had_errors = true;
+                '\u{FFFD}'
+            } else {
+                char::from_u32(code).unwrap_or('\u{FFFD}')
+            };
+            dst.push(ch);
+        }
+
+        let remainder = chunks.remainder().to_vec();
+        if is_last {
+            if !remainder.is_empty() {
+                dst.push('\u{FFFD}');
+                had_errors = true;
+            }
+            self.pending.clear();
+        } else {
+            self.pending = remainder;
+        }
+
+        had_errors
+    }
+}
+
+struct Utf32Encoder {
+    endianness: Utf32Endianness,
+}
+
+impl Utf32Encoder {
+    fn encode_from_str(&self, src: &str, dst: &mut Vec<u8>) {
+        for ch in src.chars() {
+            let code = ch as u32;
+            let bytes = match self.endianness {
+                Utf32Endianness::Little => code.to_le_bytes(),
+                Utf32Endianness::Big => code.to_be_bytes(),
+            };
+            dst.extend_from_slice(&bytes);
+        }
+    }
+}
+
+// `encoding_rs` can decode UTF-16LE/BE (real decoders exist for it), but its
+// `Encoder` has no UTF-16 support: per the WHATWG encoding spec, UTF-16 has
+// no defined "encode" steps, so `new_encoder()`/`encode()` on a UTF-16
+// `Encoding` silently fall back to UTF-8 output. Mirrors `Utf32Encoder`
+// above to give `normalize_file_stream` a real UTF-16 target codec.
+struct Utf16Encoder {
+    endianness: Utf32Endianness,
+}
+
+impl Utf16Encoder {
+    fn encode_from_str(&self, src: &str, dst: &mut Vec<u8>) {
+        for unit in src.encode_utf16() {
+            let bytes = match self.endianness {
+                Utf32Endianness::Little => unit.to_le_bytes(),
+                Utf32Endianness::Big => unit.to_be_bytes(),
+            };
+            dst.extend_from_slice(&bytes);
+        }
+    }
+}
+
+// Either a plain `encoding_rs` codec, our self-contained UTF-32 one, or
+// `encoding_rs`'s UTF-16 decoder paired with our own UTF-16 encoder.
+enum ResolvedEncoding {
+    Utf32(Utf32Endianness),
+    Utf16(Utf32Endianness, &'static encoding_rs::Encoding),
+    EncodingRs(&'static encoding_rs::Encoding),
+}
+
+fn resolve_encoding(name: &str) -> Option<ResolvedEncoding> {
+    let normalized = name.to_lowercase().replace('-', "_");
+    match normalized.as_str() {
+        "utf_32le" | "utf_32_le" | "utf32le" => {
+            Some(ResolvedEncoding::Utf32(Utf32Endianness::Little))
+        }
+        "utf_32be" | "utf_32_be" | "utf32be" => Some(ResolvedEncoding::Utf32(Utf32Endianness::Big)),
+        "utf_16" | "utf16" | "utf_16le" | "utf_16_le" | "utf16le" => Some(ResolvedEncoding::Utf16(
+            Utf32Endianness::Little,
+            encoding_rs::UTF_16LE,
+        )),
+        "utf_16be" | "utf_16_be" | "utf16be" => Some(ResolvedEncoding::Utf16(
+            Utf32Endianness::Big,
+            encoding_rs::UTF_16BE,
+        )),
+        _ => get_encoding_rs(name).map(ResolvedEncoding::EncodingRs),
+    }
+}
+
+enum SourceDecoder {
+    EncodingRs(encoding_rs::Decoder),
+    Utf32(Utf32Decoder),
+}
+
+impl SourceDecoder {
+    // Returns whether any byte in `src` failed to decode cleanly (and was
+    // replaced with U+FFFD), so callers can enforce a strict error policy.
+    fn decode_to_string(&mut self, src: &[u8], dst: &mut String, is_last: bool) -> PyResult<bool> {
+        match self {
+            SourceDecoder::EncodingRs(decoder) => {
+                let (result, _, had_errors) = decoder.decode_to_string(src, dst, is_last);
+                if result == encoding_rs::CoderResult::OutputFull {
+                    return Err(PyIOError::new_err("Decode buffer too small"));
+                }
+                Ok(had_errors)
+            }
+            SourceDecoder::Utf32(decoder) => Ok(decoder.decode_to_string(src, dst, is_last)),
+        }
+    }
+}
+
+enum TargetEncoder {
+    EncodingRs(encoding_rs::Encoder),
+    Utf32(Utf32Encoder),
+    Utf16(Utf16Encoder),
+}
+
 /// Normalize a file by converting its encoding and newline style using streaming
 ///
 /// This function processes files in chunks to maintain constant memory usage,
 /// making it suitable for very large files (10GB+) on systems with limited RAM (512MB).
+/// `locale` is forwarded to `analyse_from_path_stream` to bias source-encoding
+/// detection towards a known region/language family. `target_normalization`
+/// optionally applies Unicode normalization (`"NFC"`, `"NFD"`, `"NFKC"`,
+/// `"NFKD"`) to the decoded text before it is re-encoded. Both the source and
+/// target encoding may be UTF-32LE/BE, which `encoding_rs` itself can't handle.
+/// When `stream_safe` is set, the output is kept within the UAX #15
+/// Stream-Safe Text Process bound by inserting a combining grapheme joiner
+/// after every run of 30 consecutive non-starter characters. `compression`
+/// selects `"gzip"`/`"zstd"` decompression of the input before it's analysed
+/// and decoded; left as `None`, the compression (if any) is sniffed from the
+/// file's leading magic bytes. `transfer_encoding` additionally unwraps a
+/// MIME Content-Transfer-Encoding (`"base64"` or `"quoted-printable"`)
+/// before analysis and decoding, for normalizing raw MIME part bodies
+/// directly. `http_chunked` strips HTTP `Transfer-Encoding: chunked`
+/// framing before any of the above, so a raw captured HTTP response body
+/// can be normalized without a separate dechunking step. When a chunk is
+/// entirely ASCII, both the source and target encodings are
+/// ASCII-compatible, and no `target_normalization` is set, decoding and
+/// re-encoding are skipped in favor of a direct byte-level newline rewrite.
+/// `emit_bom`, when true, writes the correct byte-order mark for
+/// BOM-capable targets (UTF-8, UTF-16LE/BE, UTF-32LE/BE) before the first
+/// output byte. `errors` controls decode-error handling: `"replace"` (the
+/// default) silently substitutes U+FFFD, same as before this option
+/// existed; `"strict"` raises a `PyIOError` reporting the approximate byte
+/// offset of the first chunk containing a malformed sequence.
 #[pyfunction]
-#[pyo3(signature = (file_path, output_path, target_encoding="utf-8", target_newlines="LF", min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None))]
+#[pyo3(signature = (file_path, output_path, target_encoding="utf-8", target_newlines="LF", min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None, locale=None, target_normalization=None, stream_safe=false, compression=None, transfer_encoding=None, http_chunked=false, emit_bom=false, errors="replace"))]
 fn normalize_file_stream(
     file_path: String,
     output_path: String,
@@ -606,6 +1897,14 @@ fn normalize_file_stream(
     min_sample_size: usize,
     percentage_sample_size: f64,
     max_sample_size: Option<usize>,
+    locale: Option<String>,
+    target_normalization: Option<&str>,
+    stream_safe: bool,
+    compression: Option<String>,
+    transfer_encoding: Option<String>,
+    http_chunked: bool,
+    emit_bom: bool,
+    errors: &str,
 ) -> PyResult<()> {
     // Validate target_newlines
     let newline_bytes: &[u8] = match target_newlines {
@@ -620,41 +1919,109 @@ fn normalize_file_stream(
         }
     };
 
+    if errors != "strict" && errors != "replace" {
+        return Err(PyIOError::new_err(format!(
+            "Unknown errors mode '{}'. Must be 'strict' or 'replace'",
+            errors
+        )));
+    }
+
+    let normalization_form = target_normalization.map(parse_normalization_form).transpose()?;
+
     // First, analyse the file to detect source encoding
     let analysis = analyse_from_path_stream(
         file_path.clone(),
         min_sample_size,
         percentage_sample_size,
         max_sample_size,
+        locale,
+        compression.as_deref(),
+        transfer_encoding.as_deref(),
+        http_chunked,
     )?;
 
     // Get source and target encodings
-    let source_encoding = get_encoding_rs(&analysis.encoding).ok_or_else(|| {
+    let source_resolved = resolve_encoding(&analysis.encoding).ok_or_else(|| {
         PyIOError::new_err(format!(
             "Unsupported source encoding: {}",
             analysis.encoding
         ))
     })?;
 
-    let target_encoding_rs = get_encoding_rs(target_encoding).ok_or_else(|| {
+    let target_resolved = resolve_encoding(target_encoding).ok_or_else(|| {
         PyIOError::new_err(format!("Unsupported target encoding: {}", target_encoding))
     })?;
 
-    // Open input and output files
-    let input_path = Path::new(&file_path);
+    // Bytes below 0x80 encode identically across every ASCII-compatible
+    // encoding, so an all-ASCII chunk can skip decode/encode entirely and go
+    // straight through a byte-level newline rewrite. Captured before
+    // `target_resolved` is consumed by the `encoder` match below.
+    let target_ascii_compatible = matches!(
+        &target_resolved,
+        ResolvedEncoding::EncodingRs(encoding) if encoding.is_ascii_compatible()
+    );
+
+    // The fast path is only sound if the *source* is also ASCII-compatible:
+    // a chunk from e.g. UTF-16LE/BE or UTF-32 text that's all-ASCII still
+    // has every other byte be 0x00, so `encoding_rs::mem::is_ascii(chunk)`
+    // would be true even though those bytes aren't the decoded text.
+    // Captured before `source_resolved` is consumed by the `decoder` match
+    // below.
+    let source_ascii_compatible = matches!(
+        &source_resolved,
+        ResolvedEncoding::EncodingRs(encoding) if encoding.is_ascii_compatible()
+    );
+
+    // Same rationale: read off the BOM for this target before
+    // `target_resolved` is moved into `encoder`.
+    let bom_bytes: &[u8] = if !emit_bom {
+        &[]
+    } else {
+        match &target_resolved {
+            ResolvedEncoding::Utf32(Utf32Endianness::Little) => &[0xFF, 0xFE, 0x00, 0x00],
+            ResolvedEncoding::Utf32(Utf32Endianness::Big) => &[0x00, 0x00, 0xFE, 0xFF],
+            ResolvedEncoding::Utf16(Utf32Endianness::Little, _) => &[0xFF, 0xFE],
+            ResolvedEncoding::Utf16(Utf32Endianness::Big, _) => &[0xFE, 0xFF],
+            ResolvedEncoding::EncodingRs(encoding) => match encoding.name() {
+                "UTF-8" => &[0xEF, 0xBB, 0xBF],
+                "UTF-16LE" => &[0xFF, 0xFE],
+                "UTF-16BE" => &[0xFE, 0xFF],
+                _ => &[],
+            },
+        }
+    };
+
+    // Open input (transparently decompressed, if applicable) and output files
     let output_path_obj = Path::new(&output_path);
 
-    let input_file = File::open(input_path)
-        .map_err(|e| PyIOError::new_err(format!("Failed to open input file: {}", e)))?;
+    let (mut reader, _) = open_decoded(
+        &file_path,
+        compression.as_deref(),
+        transfer_encoding.as_deref(),
+        http_chunked,
+    )?;
     let output_file = File::create(output_path_obj)
         .map_err(|e| PyIOError::new_err(format!("Failed to create output file: {}", e)))?;
 
-    let mut reader = BufReader::new(input_file);
     let mut writer = BufWriter::new(output_file);
 
+    if !bom_bytes.is_empty() {
+        writer
+            .write_all(bom_bytes)
+            .map_err(|e| PyIOError::new_err(format!("Failed to write to output file: {}", e)))?;
+    }
+
     // Create decoder and encoder
-    let mut decoder = source_encoding.new_decoder();
-    let mut encoder = target_encoding_rs.new_encoder();
+    let mut decoder = match source_resolved {
+        ResolvedEncoding::Utf32(endianness) => SourceDecoder::Utf32(Utf32Decoder::new(endianness)),
+        ResolvedEncoding::Utf16(_, encoding) => SourceDecoder::EncodingRs(encoding.new_decoder()),
+        ResolvedEncoding::EncodingRs(encoding) => SourceDecoder::EncodingRs(encoding.new_decoder()),
+    };
+    let mut encoder = match target_resolved {
+        ResolvedEncoding::Utf32(endianness) => TargetEncoder::Utf32(Utf32Encoder { endianness }),
+        ResolvedEncoding::Utf16(endianness, _) => TargetEncoder::Utf16(Utf16Encoder { endianness }),
+        ResolvedEncoding::EncodingRs(encoding) => TargetEncoder::EncodingRs(encoding.new_encoder()),
+    };
 
     // Buffers for streaming processing
     let mut input_buffer = vec![0u8; CHUNK_SIZE];
@@ -663,6 +2030,14 @@ fn normalize_file_stream(
 
     // State for newline conversion
     let mut pending_cr = false; // Track if previous chunk ended with CR
+    // State for normalization: a combining sequence held back across chunks
+    let mut normalization_carry = String::new();
+    // State for the Stream-Safe Text Process bound: length of the run of
+    // consecutive non-starters seen so far, carried across chunk boundaries.
+    let mut consecutive_non_starters: u32 = 0;
+    // Cumulative count of input bytes consumed so far, used under `errors =
+    // "strict"` to report an approximate offset for the first bad chunk.
+    let mut bytes_consumed: u64 = 0;
 
     loop {
         // Read chunk from input
@@ -671,25 +2046,52 @@ fn normalize_file_stream(
             .map_err(|e| PyIOError::new_err(format!("Failed to read from input file: {}", e)))?;
 
         let is_last = bytes_read == 0;
+        let chunk = &input_buffer[..bytes_read];
+
+        // Fast path: a chunk that's entirely ASCII needs no decode/encode at
+        // all when the target is ASCII-compatible, since those bytes are
+        // identical on both sides. Skipped on the last (possibly empty)
+        // read so the decoder and normalization carry always get a chance
+        // to flush their final state.
+        if !is_last && source_ascii_compatible && target_ascii_compatible
+            && normalization_form.is_none()
+            && encoding_rs::mem::is_ascii(chunk)
+        {
+            write_ascii_chunk_with_newlines(chunk, &mut writer, newline_bytes, &mut pending_cr)?;
+            consecutive_non_starters = 0;
+            bytes_consumed += bytes_read as u64;
+            continue;
+        }
 
         // Decode chunk
         decode_buffer.clear();
-        let (result, _bytes_read, _had_errors) =
-            decoder.decode_to_string(&input_buffer[..bytes_read], &mut decode_buffer, is_last);
-
-        if result == encoding_rs::CoderResult::OutputFull {
-            return Err(PyIOError::new_err("Decode buffer too small"));
+        let had_decode_errors = decoder.decode_to_string(chunk, &mut decode_buffer, is_last)?;
+        if had_decode_errors && errors == "strict" {
+            return Err(PyIOError::new_err(format!(
+                "Failed to decode file with encoding: malformed sequence near byte offset {}",
+                bytes_consumed
+            )));
         }
+        bytes_consumed += bytes_read as u64;
+
+        let normalized_chunk = apply_normalization(
+            normalization_form,
+            &mut normalization_carry,
+            &decode_buffer,
+            is_last,
+        );
 
         // Process and write decoded chunk with newline conversion
-        if !decode_buffer.is_empty() {
+        if !normalized_chunk.is_empty() {
             process_and_write_chunk(
-                &decode_buffer,
+                &normalized_chunk,
                 &mut encoder,
                 &mut encode_buffer,
                 &mut writer,
                 newline_bytes,
                 &mut pending_cr,
+                stream_safe,
+                &mut consecutive_non_starters,
                 is_last,
             )?;
         }
@@ -707,14 +2109,78 @@ fn normalize_file_stream(
     Ok(())
 }
 
+// Byte-level counterpart of process_and_write_chunk's newline rewriting,
+// used by normalize_file_stream's all-ASCII fast path. Since every byte here
+// is already target-representable as-is, this skips decoding/encoding
+// entirely and just rewrites CR/LF/CRLF sequences in place.
+fn write_ascii_chunk_with_newlines(
+    chunk: &[u8],
+    writer: &mut BufWriter<File>,
+    newline_bytes: &[u8],
+    pending_cr: &mut bool,
+) -> PyResult<()> {
+    let mut output = Vec::with_capacity(chunk.len());
+    let mut i = 0;
+
+    if *pending_cr {
+        // Previous chunk ended with a lone CR; this chunk's first byte
+        // decides whether it was standalone or the other half of a CRLF.
+        output.extend_from_slice(newline_bytes);
+        *pending_cr = false;
+        if chunk.first() == Some(&b'\n') {
+            i = 1;
+        }
+    }
+
+    while i < chunk.len() {
+        match chunk[i] {
+            b'\r' => {
+                if i + 1 < chunk.len() && chunk[i + 1] == b'\n' {
+                    output.extend_from_slice(newline_bytes);
+                    i += 2;
+                } else if i + 1 == chunk.len() {
+                    // CR at the very end: might be half of a CRLF split
+                    // across chunks, so defer it to the next read.
+                    *pending_cr = true;
+                    i += 1;
+                } else {
+                    output.extend_from_slice(newline_bytes);
+                    i += 1;
+                }
+            }
+            b'\n' => {
+                output.extend_from_slice(newline_bytes);
+                i += 1;
+            }
+            byte => {
+                output.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    writer
+        .write_all(&output)
+        .map_err(|e| PyIOError::new_err(format!("Failed to write to output file: {}", e)))?;
+    Ok(())
+}
+
+// UAX #15 Stream-Safe Text Process bound: no more than this many consecutive
+// non-starter (combining class != 0) chars are allowed before a COMBINING
+// GRAPHEME JOINER is inserted to cap the run.
+const STREAM_SAFE_MAX_NON_STARTERS: u32 = 30;
+const COMBINING_GRAPHEME_JOINER: char = '\u{034F}';
+
 // Helper function to process a text chunk, convert newlines, and write to output
 fn process_and_write_chunk(
     text: &str,
-    encoder: &mut encoding_rs::Encoder,
+    encoder: &mut TargetEncoder,
     encode_buffer: &mut Vec<u8>,
     writer: &mut BufWriter<File>,
     newline_bytes: &[u8],
     pending_cr: &mut bool,
+    stream_safe: bool,
+    consecutive_non_starters: &mut u32,
     is_last: bool,
 ) -> PyResult<()> {
     // Convert newline bytes to string once (safe because we validate newline_bytes is valid UTF-8)
@@ -732,11 +2198,13 @@ fn process_and_write_chunk(
                 // This is CRLF split across chunks, output target newline
                 output.push_str(newline_str);
                 *pending_cr = false;
+                *consecutive_non_starters = 0;
                 continue;
             } else {
                 // Previous CR was standalone, output it and continue
                 output.push_str(newline_str);
                 *pending_cr = false;
+                *consecutive_non_starters = 0;
             }
         }
 
@@ -746,12 +2214,14 @@ fn process_and_write_chunk(
                 if i + 1 < chars.len() && chars[i + 1] == '\n' {
                     // CRLF - will handle \n in next iteration
                     output.push_str(newline_str);
+                    *consecutive_non_starters = 0;
                 } else if i + 1 == chars.len() && !is_last {
                     // CR at end of chunk, might be part of CRLF
                     *pending_cr = true;
                 } else {
                     // Standalone CR
                     output.push_str(newline_str);
+                    *consecutive_non_starters = 0;
                 }
             }
             '\n' => {
@@ -762,9 +2232,21 @@ fn process_and_write_chunk(
                 } else {
                     // Standalone LF
                     output.push_str(newline_str);
+                    *consecutive_non_starters = 0;
                 }
             }
             _ => {
+                if stream_safe {
+                    if canonical_combining_class(ch) == 0 {
+                        *consecutive_non_starters = 0;
+                    } else {
+                        if *consecutive_non_starters >= STREAM_SAFE_MAX_NON_STARTERS {
+                            output.push(COMBINING_GRAPHEME_JOINER);
+                            *consecutive_non_starters = 0;
+                        }
+                        *consecutive_non_starters += 1;
+                    }
+                }
                 output.push(ch);
             }
         }
@@ -772,34 +2254,386 @@ fn process_and_write_chunk(
 
     // Encode and write the processed text
     if !output.is_empty() {
-        let mut start = 0;
-        loop {
-            let (result, bytes_read, bytes_written, _had_errors) =
-                encoder.encode_from_utf8(&output[start..], encode_buffer, is_last);
-
-            // Write encoded bytes
-            if bytes_written > 0 {
+        match encoder {
+            TargetEncoder::EncodingRs(encoder) => {
+                let mut start = 0;
+                loop {
+                    let (result, bytes_read, bytes_written, _had_errors) =
+                        encoder.encode_from_utf8(&output[start..], encode_buffer, is_last);
+
+                    // Write encoded bytes
+                    if bytes_written > 0 {
+                        writer.write_all(&encode_buffer[..bytes_written]).map_err(|e| {
+                            PyIOError::new_err(format!("Failed to write to output: {}", e))
+                        })?;
+                    }
+
+                    start += bytes_read;
+
+                    if result == encoding_rs::CoderResult::InputEmpty {
+                        break;
+                    }
+                }
+            }
+            TargetEncoder::Utf32(encoder) => {
+                let mut bytes = Vec::with_capacity(output.len() * 4);
+                encoder.encode_from_str(&output, &mut bytes);
+                writer
+                    .write_all(&bytes)
+                    .map_err(|e| PyIOError::new_err(format!("Failed to write to output: {}", e)))?;
+            }
+            TargetEncoder::Utf16(encoder) => {
+                let mut bytes = Vec::with_capacity(output.len() * 2);
+                encoder.encode_from_str(&output, &mut bytes);
                 writer
-                    .write_all(&encode_buffer[..bytes_written])
+                    .write_all(&bytes)
                     .map_err(|e| PyIOError::new_err(format!("Failed to write to output: {}", e)))?;
             }
+        }
+    }
 
-            start += bytes_read;
+    Ok(())
+}
 
-            if result == encoding_rs::CoderResult::InputEmpty {
-                break;
+/// Maps UTF-8 byte offsets to zero-based (line, column) in either UTF-8 or
+/// UTF-16 column conventions, modeled on the LineIndex/WideEncoding design
+/// used by editor and LSP tooling that must reconcile UTF-16 column
+/// conventions with the UTF-8 bytes this crate already reads.
+#[pyclass]
+struct LineIndex {
+    // Byte offset of the start of each line; line 0 always starts at 0.
+    line_starts: Vec<usize>,
+    // Per line, the (byte offset within the line, utf8 len, utf16 len) of
+    // every char whose UTF-8 and UTF-16 lengths differ, needed to translate
+    // a byte offset into a UTF-16 code unit offset on that line.
+    wide_chars: HashMap<usize, Vec<(usize, u8, u8)>>,
+    #[pyo3(get)]
+    total_lines: usize,
+    #[pyo3(get)]
+    longest_line_utf8: usize,
+    #[pyo3(get)]
+    longest_line_utf16: usize,
+}
+
+impl LineIndex {
+    fn line_for_offset(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        }
+    }
+}
+
+#[pymethods]
+impl LineIndex {
+    /// Translate a byte offset into zero-based (line, column), with column
+    /// measured in UTF-8 bytes.
+    fn line_col_utf8(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_for_offset(offset);
+        (line, offset - self.line_starts[line])
+    }
+
+    /// Translate a byte offset into zero-based (line, column), with column
+    /// measured in UTF-16 code units.
+    fn line_col_utf16(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_for_offset(offset);
+        let byte_col = offset - self.line_starts[line];
+
+        let mut col = byte_col as isize;
+        if let Some(wide) = self.wide_chars.get(&line) {
+            for &(wide_offset, utf8_len, utf16_len) in wide {
+                if wide_offset < byte_col {
+                    col -= utf8_len as isize - utf16_len as isize;
+                }
             }
         }
+
+        (line, col.max(0) as usize)
     }
 
-    Ok(())
+    fn __repr__(&self) -> String {
+        format!(
+            "LineIndex(total_lines={}, longest_line_utf8={}, longest_line_utf16={})",
+            self.total_lines, self.longest_line_utf8, self.longest_line_utf16
+        )
+    }
+}
+
+fn build_line_index(text: &str) -> LineIndex {
+    let mut line_starts = vec![0usize];
+    let mut wide_chars: HashMap<usize, Vec<(usize, u8, u8)>> = HashMap::new();
+
+    let mut line_start = 0usize;
+    let mut line_utf8_len = 0usize;
+    let mut line_utf16_len = 0usize;
+    let mut longest_utf8 = 0usize;
+    let mut longest_utf16 = 0usize;
+    let mut current_line = 0usize;
+
+    for (offset, ch) in text.char_indices() {
+        let utf8_len = ch.len_utf8() as u8;
+        let utf16_len = ch.len_utf16() as u8;
+
+        if ch == '\n' {
+            longest_utf8 = longest_utf8.max(line_utf8_len);
+            longest_utf16 = longest_utf16.max(line_utf16_len);
+            line_start = offset + ch.len_utf8();
+            line_starts.push(line_start);
+            line_utf8_len = 0;
+            line_utf16_len = 0;
+            current_line += 1;
+            continue;
+        }
+
+        if utf8_len != utf16_len {
+            wide_chars
+                .entry(current_line)
+                .or_default()
+                .push((offset - line_start, utf8_len, utf16_len));
+        }
+
+        line_utf8_len += utf8_len as usize;
+        line_utf16_len += utf16_len as usize;
+    }
+    longest_utf8 = longest_utf8.max(line_utf8_len);
+    longest_utf16 = longest_utf16.max(line_utf16_len);
+
+    LineIndex {
+        total_lines: line_starts.len(),
+        longest_line_utf8: longest_utf8,
+        longest_line_utf16: longest_utf16,
+        line_starts,
+        wide_chars,
+    }
+}
+
+/// Builds a `LineIndex` over the sampled (or, with `full_file=true`, the
+/// entire) file: total line count, the longest line in both UTF-8 bytes and
+/// UTF-16 code units, and byte-offset-to-(line, column) lookups in either
+/// convention. This fits alongside the existing newline-style detection and
+/// is aimed at editors/LSP-style tools that must reconcile UTF-16 column
+/// conventions with the UTF-8 bytes this crate reads.
+#[pyfunction]
+#[pyo3(signature = (file_path, min_sample_size=1024*1024, percentage_sample_size=0.1, max_sample_size=None, locale=None, full_file=false))]
+fn analyse_line_index_from_path_stream(
+    file_path: String,
+    min_sample_size: usize,
+    percentage_sample_size: f64,
+    max_sample_size: Option<usize>,
+    locale: Option<String>,
+    full_file: bool,
+) -> PyResult<LineIndex> {
+    let analysis = analyse_from_path_stream(
+        file_path.clone(),
+        min_sample_size,
+        percentage_sample_size,
+        max_sample_size,
+        locale,
+        None,
+        None,
+        false,
+    )?;
+    let encoding = get_encoding_rs(&analysis.encoding).ok_or_else(|| {
+        PyIOError::new_err(format!("Unsupported encoding: {}", analysis.encoding))
+    })?;
+
+    let file = File::open(Path::new(&file_path))
+        .map_err(|e| PyIOError::new_err(format!("Failed to open file: {}", e)))?;
+
+    let buffer = if full_file {
+        let mut file = file;
+        let mut buffer = Vec::new();
+        file.read_to_end(&mut buffer)
+            .map_err(|e| PyIOError::new_err(format!("Failed to read file: {}", e)))?;
+        buffer
+    } else {
+        let file_size = file
+            .metadata()
+            .map_err(|e| PyIOError::new_err(format!("Failed to get file metadata: {}", e)))?
+            .len();
+        let sample_size = calculate_sample_size(
+            file_size,
+            min_sample_size,
+            percentage_sample_size,
+            max_sample_size,
+        );
+        let mut reader = BufReader::new(file);
+        read_strategic_sample(&mut reader, file_size, sample_size)
+            .map_err(|e| PyIOError::new_err(format!("Failed to read file: {}", e)))?
+    };
+
+    let (decoded, _, _) = encoding.decode(&buffer);
+    Ok(build_line_index(&decoded))
 }
 
 /// A Python module implemented in Rust.
 #[pymodule]
 fn _internal(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(analyse_from_path_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(analyse_bytes, m)?)?;
+    m.add_function(wrap_pyfunction!(analyse_candidates_from_path_stream, m)?)?;
+    m.add_function(wrap_pyfunction!(analyse_candidates, m)?)?;
+    m.add_function(wrap_pyfunction!(analyse_line_index_from_path_stream, m)?)?;
     m.add_function(wrap_pyfunction!(normalize_file_stream, m)?)?;
     m.add_class::<AnalysisResult>()?;
+    m.add_class::<AnalysisCandidate>()?;
+    m.add_class::<LineIndex>()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf32_decoder_strips_leading_bom() {
+        let mut bytes = vec![0xFF, 0xFE, 0x00, 0x00]; // U+FEFF in UTF-32LE
+        Utf32Encoder {
+            endianness: Utf32Endianness::Little,
+        }
+        .encode_from_str("hi", &mut bytes);
+
+        let mut decoder = Utf32Decoder::new(Utf32Endianness::Little);
+        let mut decoded = String::new();
+        let had_errors = decoder.decode_to_string(&bytes, &mut decoded, true);
+
+        assert!(!had_errors);
+        assert_eq!(decoded, "hi");
+    }
+
+    #[test]
+    fn normalize_file_stream_round_trips_utf32le_source_to_utf8() {
+        let dir = std::env::temp_dir();
+        let pid = std::process::id();
+        let input_path = dir.join(format!("charsetrs_test_utf32le_in_{}.txt", pid));
+        let output_path = dir.join(format!("charsetrs_test_utf32le_out_{}.txt", pid));
+
+        let mut input_bytes = vec![0xFF, 0xFE, 0x00, 0x00]; // UTF-32LE BOM
+        Utf32Encoder {
+            endianness: Utf32Endianness::Little,
+        }
+        .encode_from_str("Hello, world!\n", &mut input_bytes);
+        std::fs::write(&input_path, &input_bytes).unwrap();
+
+        let result = normalize_file_stream(
+            input_path.to_str().unwrap().to_string(),
+            output_path.to_str().unwrap().to_string(),
+            "utf-8",
+            "LF",
+            1024 * 1024,
+            0.1,
+            None,
+            None,
+            None,
+            false,
+            None,
+            None,
+            false,
+            false,
+            "replace",
+        );
+
+        let output = std::fs::read_to_string(&output_path).unwrap();
+
+        let _ = std::fs::remove_file(&input_path);
+        let _ = std::fs::remove_file(&output_path);
+
+        result.unwrap();
+        assert_eq!(output, "Hello, world!\n");
+    }
+
+    #[test]
+    fn utf32_round_trip_both_endiannesses() {
+        let text = "Hello, \u{1F600} world!";
+        for endianness in [Utf32Endianness::Little, Utf32Endianness::Big] {
+            let mut bytes = Vec::new();
+            Utf32Encoder { endianness }.encode_from_str(text, &mut bytes);
+
+            let mut decoder = Utf32Decoder::new(endianness);
+            let mut decoded = String::new();
+            let had_errors = decoder.decode_to_string(&bytes, &mut decoded, true);
+
+            assert!(!had_errors);
+            assert_eq!(decoded, text);
+        }
+    }
+
+    #[test]
+    fn utf32_decoder_replaces_truncated_trailing_bytes() {
+        let mut decoder = Utf32Decoder::new(Utf32Endianness::Little);
+        let mut decoded = String::new();
+        // 'A' (4 bytes) followed by 2 stray bytes that never complete a group.
+        let had_errors = decoder.decode_to_string(&[0x41, 0, 0, 0, 0xFF, 0xFE], &mut decoded, true);
+
+        assert!(had_errors);
+        assert_eq!(decoded, "A\u{FFFD}");
+    }
+
+    #[test]
+    fn utf16_encoder_round_trips_through_encoding_rs_decoder() {
+        let text = "Hello, \u{1F600} world!";
+        for (endianness, encoding) in [
+            (Utf32Endianness::Little, encoding_rs::UTF_16LE),
+            (Utf32Endianness::Big, encoding_rs::UTF_16BE),
+        ] {
+            let mut bytes = Vec::new();
+            Utf16Encoder { endianness }.encode_from_str(text, &mut bytes);
+
+            let (decoded, _, had_errors) = encoding.decode(&bytes);
+            assert!(!had_errors);
+            assert_eq!(decoded, text);
+        }
+    }
+
+    #[test]
+    fn decode_base64_groups_handles_full_and_padded_groups() {
+        // "Man" -> "TWFu"
+        assert_eq!(decode_base64_groups(b"TWFu"), b"Man");
+        // "Ma" -> "TWE=" (one padding char, 2 output bytes)
+        assert_eq!(decode_base64_groups(b"TWE="), b"Ma");
+        // "M" -> "TQ==" (two padding chars, 1 output byte)
+        assert_eq!(decode_base64_groups(b"TQ=="), b"M");
+    }
+
+    #[test]
+    fn decode_base64_groups_handles_unpadded_short_trailing_group_without_panicking() {
+        // A stream that ends without `=` padding: "TQ" is an unpadded
+        // trailing 2-character group and should decode like "TQ==" rather
+        // than panic on out-of-bounds indexing.
+        assert_eq!(decode_base64_groups(b"TQ"), b"M");
+        // A lone leftover character can't encode a byte and is dropped.
+        assert_eq!(decode_base64_groups(b"T"), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn detect_encoding_candidates_prefers_utf32le_bom_over_utf16le() {
+        let buffer = [0xFF, 0xFE, 0x00, 0x00, 0x41, 0, 0, 0];
+        let (encoding, skip_bytes, _, _) = detect_encoding_candidates(&buffer);
+
+        assert_eq!(encoding, "UTF-32LE");
+        assert_eq!(skip_bytes, 4);
+    }
+
+    #[test]
+    fn detect_encoding_candidates_still_detects_utf16le_bom() {
+        let buffer = [0xFF, 0xFE, 0x41, 0x00];
+        let (encoding, skip_bytes, _, _) = detect_encoding_candidates(&buffer);
+
+        assert_eq!(encoding, "UTF-16LE");
+        assert_eq!(skip_bytes, 2);
+    }
+
+    #[test]
+    fn apply_normalization_composes_hangul_jamo_split_across_chunks() {
+        let mut carry = String::new();
+        // Leading jamo U+1100 and vowel jamo U+1161 compose into the
+        // syllable U+AC00 ("가") via NFC, but only if they're seen
+        // together; a chunk boundary between them must not treat the L as
+        // already-stable.
+        let first = apply_normalization(Some(NormalizationForm::Nfc), &mut carry, "\u{1100}", false);
+        assert_eq!(first, "");
+
+        let second = apply_normalization(Some(NormalizationForm::Nfc), &mut carry, "\u{1161}", true);
+        assert_eq!(second, "\u{AC00}");
+    }
+}